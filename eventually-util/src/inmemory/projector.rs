@@ -1,7 +1,10 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use eventually_core::projection::Projection;
 use eventually_core::store::{EventStore, Select};
@@ -9,14 +12,292 @@ use eventually_core::subscription::EventSubscriber;
 
 use futures::stream::{Stream, StreamExt, TryStreamExt};
 
+use tokio::sync::mpsc;
 use tokio::sync::watch::{channel, Receiver, Sender};
 
+/// Restores and persists the progress of a [`Projector`], so that it can
+/// resume from where it left off instead of replaying the whole
+/// [`EventStore`] on every restart.
+///
+/// Implementations are expected to key checkpoints by the `projection_id`
+/// passed to [`ProjectorBuilder::with_checkpoint_store`], so that a single
+/// backing store can be shared by several projections.
+///
+/// [`Projector`]: struct.Projector.html
+/// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+/// [`ProjectorBuilder::with_checkpoint_store`]: struct.ProjectorBuilder.html#method.with_checkpoint_store
+#[async_trait::async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Loads the last checkpointed sequence number for the given
+    /// `projection_id`, or `None` if the projection has never checkpointed
+    /// before.
+    async fn load(&self, projection_id: &str) -> Option<u32>;
+
+    /// Persists `sequence_number` as the latest checkpoint reached by the
+    /// projection identified by `projection_id`.
+    async fn save(&self, projection_id: &str, sequence_number: u32) -> anyhow::Result<()>;
+}
+
+/// Controls how often a [`Projector`] persists its checkpoint through a
+/// configured [`CheckpointStore`], trading off replay distance after a crash
+/// against the cost of writing to the checkpoint store.
+///
+/// [`Projector`]: struct.Projector.html
+/// [`CheckpointStore`]: trait.CheckpointStore.html
+#[derive(Debug, Clone, Copy)]
+pub enum FlushCadence {
+    /// Persist the checkpoint after every `n` projected events.
+    EveryEvents(u32),
+
+    /// Persist the checkpoint at most once per the provided [`Duration`],
+    /// regardless of how many events have been projected in between.
+    ///
+    /// [`Duration`]: std::time::Duration
+    EveryInterval(Duration),
+}
+
+impl Default for FlushCadence {
+    fn default() -> Self {
+        FlushCadence::EveryEvents(100)
+    }
+}
+
+#[derive(Clone)]
+struct CheckpointConfig {
+    store: Arc<dyn CheckpointStore>,
+    projection_id: String,
+    cadence: FlushCadence,
+}
+
+/// The current status of a [`Projector`], telling apart the initial
+/// catch-up phase -- where history is being replayed from the
+/// [`EventStore`] -- from steady-state operation, where the `Projector` is
+/// tailing the live [`EventSubscriber`] stream.
+///
+/// Obtainable through [`Projector::watch_status`].
+///
+/// [`Projector`]: struct.Projector.html
+/// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+/// [`EventSubscriber`]: ../../../eventually-core/subscription/trait.EventSubscriber.html
+/// [`Projector::watch_status`]: struct.Projector.html#method.watch_status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectorStatus {
+    /// The `Projector` is still replaying the one-off stream from the
+    /// `EventStore`.
+    CatchingUp {
+        /// Number of events processed during the catch-up phase so far.
+        processed: u32,
+        /// Number of stream errors and checkpoint flush failures skipped so
+        /// far because of the configured [`ErrorPolicy`]. See
+        /// [`ErrorPolicy::Skip`].
+        ///
+        /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+        /// [`ErrorPolicy::Skip`]: enum.ErrorPolicy.html#variant.Skip
+        skipped: u32,
+        /// Number of stream errors and checkpoint flush failures retried so
+        /// far because of the configured [`ErrorPolicy`]. See
+        /// [`ErrorPolicy::Retry`].
+        ///
+        /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+        /// [`ErrorPolicy::Retry`]: enum.ErrorPolicy.html#variant.Retry
+        retried: u32,
+    },
+
+    /// The one-off stream has been exhausted and the `Projector` is now
+    /// processing events from the live subscription.
+    Live {
+        /// Number of stream errors and checkpoint flush failures skipped so
+        /// far because of the configured [`ErrorPolicy`]. See
+        /// [`ErrorPolicy::Skip`].
+        ///
+        /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+        /// [`ErrorPolicy::Skip`]: enum.ErrorPolicy.html#variant.Skip
+        skipped: u32,
+        /// Number of stream errors and checkpoint flush failures retried so
+        /// far because of the configured [`ErrorPolicy`]. See
+        /// [`ErrorPolicy::Retry`].
+        ///
+        /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+        /// [`ErrorPolicy::Retry`]: enum.ErrorPolicy.html#variant.Retry
+        retried: u32,
+    },
+}
+
+impl Default for ProjectorStatus {
+    fn default() -> Self {
+        ProjectorStatus::CatchingUp {
+            processed: 0,
+            skipped: 0,
+            retried: 0,
+        }
+    }
+}
+
+/// Controls how a [`Projector`] reacts to an error coming from its event
+/// stream or from a configured [`CheckpointStore`], instead of unconditionally
+/// aborting [`run`] on the very first one.
+///
+/// [`Projector`]: struct.Projector.html
+/// [`CheckpointStore`]: trait.CheckpointStore.html
+/// [`run`]: struct.Projector.html#method.run
+#[derive(Clone)]
+pub enum ErrorPolicy {
+    /// Abort `run` as soon as a stream or checkpoint-store error is
+    /// encountered. This is the default, and matches the behavior `run` had
+    /// before `ErrorPolicy` existed.
+    Fail,
+
+    /// Skip the offending event (or checkpoint flush) and keep running. If
+    /// `log` is set, the error is routed to it instead of being silently
+    /// dropped.
+    Skip {
+        /// Optional dead-letter sink that skipped errors are routed to.
+        log: Option<Arc<dyn DeadLetterSink>>,
+    },
+
+    /// Retry instead of failing immediately: wait `backoff`, then resume
+    /// pulling from the stream (or attempt the checkpoint flush again on the
+    /// next eligible event), up to `max_attempts` consecutive errors before
+    /// giving up and aborting `run`.
+    Retry {
+        /// Maximum number of consecutive errors to retry before giving up.
+        max_attempts: u32,
+        /// How long to wait between one attempt and the next.
+        backoff: Duration,
+    },
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        ErrorPolicy::Fail
+    }
+}
+
+/// A dead-letter sink for events or errors skipped by an
+/// [`ErrorPolicy::Skip`] policy, so operators can inspect what a [`Projector`]
+/// chose not to project instead of losing track of it entirely.
+///
+/// [`ErrorPolicy::Skip`]: enum.ErrorPolicy.html#variant.Skip
+/// [`Projector`]: struct.Projector.html
+#[async_trait::async_trait]
+pub trait DeadLetterSink: Send + Sync {
+    /// Records a skipped error, from the event stream or from a checkpoint
+    /// flush.
+    async fn send(&self, error: anyhow::Error);
+}
+
+/// Distinguishes events coming from the [`Projector`]'s one-off and live
+/// streams from the sentinel marking the transition between the two, so
+/// that [`Projector::run`] can flip its [`ProjectorStatus`] at the exact
+/// point where catch-up ends and live tailing begins.
+///
+/// [`Projector`]: struct.Projector.html
+/// [`Projector::run`]: struct.Projector.html#method.run
+/// [`ProjectorStatus`]: enum.ProjectorStatus.html
+enum StreamItem<Event> {
+    Event(Event),
+    CatchUpComplete,
+}
+
+/// Whether `event_sequence_number` is a duplicate already covered by
+/// `last_sequence_number`, i.e. it fell in the overlap between a one-off
+/// catch-up stream and a live subscription opened concurrently.
+///
+/// Only sequence numbers strictly below `last_sequence_number` count as
+/// duplicates: an event whose sequence number equals it is not skipped, so
+/// the very first event (sequence number 0) passes while nothing has been
+/// seen yet.
+///
+/// Shared between [`Projector::run`] and [`ProjectorGroup::run`], which both
+/// race the two streams the same way and so need the same dedup rule.
+///
+/// [`Projector::run`]: struct.Projector.html#method.run
+/// [`ProjectorGroup::run`]: struct.ProjectorGroup.html#method.run
+fn is_duplicate_sequence(last_sequence_number: u32, event_sequence_number: u32) -> bool {
+    event_sequence_number < last_sequence_number
+}
+
+/// Whether an [`ErrorPolicy::Retry`] policy should give up and let the error
+/// propagate, given how many consecutive errors have now been seen
+/// (including the current one) and the policy's configured `max_attempts`.
+///
+/// Extracted out of [`Projector::apply_error_policy`] so the give-up
+/// threshold can be tested without needing a real [`Projector`].
+///
+/// [`ErrorPolicy::Retry`]: enum.ErrorPolicy.html#variant.Retry
+/// [`Projector::apply_error_policy`]: struct.Projector.html
+/// [`Projector`]: struct.Projector.html
+fn retry_attempts_exhausted(consecutive_errors: u32, max_attempts: u32) -> bool {
+    consecutive_errors > max_attempts
+}
+
+/// Resolves the [`Select`] a [`Projector::run`] should open its one-off
+/// stream with: resume right after `restored_checkpoint` if one was loaded,
+/// falling back to `filter_select` (the configured [`EventFilter::select`],
+/// or `Select::All`) otherwise.
+///
+/// Extracted out of [`Projector::run`] so the checkpoint-restore precedence
+/// can be tested without needing a real [`Projector`].
+///
+/// [`Select`]: ../../../eventually-core/store/enum.Select.html
+/// [`Projector::run`]: struct.Projector.html#method.run
+/// [`EventFilter::select`]: trait.EventFilter.html#method.select
+/// [`Projector`]: struct.Projector.html
+fn resume_select(restored_checkpoint: Option<u32>, filter_select: Select) -> Select {
+    match restored_checkpoint {
+        Some(last_sequence_number) => Select::From(last_sequence_number + 1),
+        None => filter_select,
+    }
+}
+
+/// Whether a checkpoint flush is due, given how many events have been
+/// projected and how long it's been since the last flush.
+///
+/// Extracted out of [`Projector::run`] so each [`FlushCadence`] variant can
+/// be tested without needing a real [`Projector`].
+///
+/// [`Projector::run`]: struct.Projector.html#method.run
+/// [`FlushCadence`]: enum.FlushCadence.html
+fn should_flush_checkpoint(cadence: FlushCadence, events_since_flush: u32, last_flush_at: Instant) -> bool {
+    match cadence {
+        FlushCadence::EveryEvents(n) => events_since_flush >= n,
+        FlushCadence::EveryInterval(interval) => last_flush_at.elapsed() >= interval,
+    }
+}
+
+/// Derives the [`ProjectorStatus`] to broadcast from `Projector::run`'s
+/// in-flight counters.
+///
+/// Extracted out of [`Projector::run`] so the catch-up/live transition
+/// can be tested without driving a real [`Projector`].
+///
+/// [`ProjectorStatus`]: enum.ProjectorStatus.html
+/// [`Projector::run`]: struct.Projector.html#method.run
+/// [`Projector`]: struct.Projector.html
+fn derive_status(catching_up: bool, processed: u32, skipped: u32, retried: u32) -> ProjectorStatus {
+    if catching_up {
+        ProjectorStatus::CatchingUp {
+            processed,
+            skipped,
+            retried,
+        }
+    } else {
+        ProjectorStatus::Live { skipped, retried }
+    }
+}
+
 /// Reusable builder for multiple [`Projector`] instances.
 ///
 /// [`Projector`]: struct.Projector.html
 pub struct ProjectorBuilder<Store, Subscriber> {
     store: Arc<Store>,
     subscriber: Arc<Subscriber>,
+    checkpoint: Option<CheckpointConfig>,
+    // Kept independently of `checkpoint` (rather than only as a field on
+    // CheckpointConfig) so with_checkpoint_flush_cadence works regardless of
+    // whether it's called before or after with_checkpoint_store.
+    checkpoint_cadence: Option<FlushCadence>,
+    error_policy: ErrorPolicy,
 }
 
 impl<Store, Subscriber> ProjectorBuilder<Store, Subscriber> {
@@ -26,7 +307,72 @@ impl<Store, Subscriber> ProjectorBuilder<Store, Subscriber> {
     /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
     /// [`EventSubscriber`]: ../../../eventually-core/subscription/trait.EventSubscriber.html
     pub fn new(store: Arc<Store>, subscriber: Arc<Subscriber>) -> Self {
-        Self { store, subscriber }
+        Self {
+            store,
+            subscriber,
+            checkpoint: None,
+            checkpoint_cadence: None,
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+
+    /// Configures a [`CheckpointStore`] for the projections built from this
+    /// builder, keyed by `projection_id`.
+    ///
+    /// A built [`Projector`] will restore its last sequence number from the
+    /// store before starting and will resume streaming from right after it,
+    /// instead of replaying the whole [`EventStore`] from the beginning.
+    ///
+    /// Checkpoints are flushed according to the [`FlushCadence::default`]
+    /// cadence unless overridden through
+    /// [`with_checkpoint_flush_cadence`] -- whether that call happens before
+    /// or after this one.
+    ///
+    /// [`CheckpointStore`]: trait.CheckpointStore.html
+    /// [`Projector`]: struct.Projector.html
+    /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+    /// [`with_checkpoint_flush_cadence`]: struct.ProjectorBuilder.html#method.with_checkpoint_flush_cadence
+    pub fn with_checkpoint_store<C>(mut self, checkpoint_store: C, projection_id: impl Into<String>) -> Self
+    where
+        C: CheckpointStore + 'static,
+    {
+        self.checkpoint = Some(CheckpointConfig {
+            store: Arc::new(checkpoint_store),
+            projection_id: projection_id.into(),
+            cadence: self.checkpoint_cadence.unwrap_or_default(),
+        });
+
+        self
+    }
+
+    /// Overrides the [`FlushCadence`] used to persist checkpoints.
+    ///
+    /// Order-independent with respect to [`with_checkpoint_store`]: calling
+    /// this before or after it produces the same cadence. Has no effect if
+    /// no checkpoint store is ever configured.
+    ///
+    /// [`FlushCadence`]: enum.FlushCadence.html
+    /// [`CheckpointStore`]: trait.CheckpointStore.html
+    /// [`with_checkpoint_store`]: struct.ProjectorBuilder.html#method.with_checkpoint_store
+    pub fn with_checkpoint_flush_cadence(mut self, cadence: FlushCadence) -> Self {
+        self.checkpoint_cadence = Some(cadence);
+
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.cadence = cadence;
+        }
+
+        self
+    }
+
+    /// Configures the [`ErrorPolicy`] that built [`Projector`]s use to react
+    /// to stream errors. Defaults to [`ErrorPolicy::Fail`].
+    ///
+    /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+    /// [`Projector`]: struct.Projector.html
+    /// [`ErrorPolicy::Fail`]: enum.ErrorPolicy.html#variant.Fail
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
     }
 
     /// Builds a new [`Projector`] for the [`Projection`]
@@ -43,7 +389,91 @@ impl<Store, Subscriber> ProjectorBuilder<Store, Subscriber> {
         <Store as EventStore>::Error: StdError + Send + Sync + 'static,
         <Subscriber as EventSubscriber>::Error: StdError + Send + Sync + 'static,
     {
-        Projector::new(self.store.clone(), self.subscriber.clone())
+        Projector::new(
+            self.store.clone(),
+            self.subscriber.clone(),
+            self.checkpoint.clone(),
+            None,
+            self.error_policy.clone(),
+        )
+    }
+
+    /// Builds a new [`Projector`] for the [`Projection`] specified in the
+    /// function type, scoped to the events matched by `filter`.
+    ///
+    /// Events for which [`EventFilter::matches`] returns `false` are
+    /// discarded before [`Projection::project`] is called. When `filter`
+    /// also overrides [`EventFilter::select`], the coarse range it returns is
+    /// pushed down to the [`EventStore`] instead of `Select::All`, so
+    /// irrelevant history is not even streamed -- as long as no checkpoint
+    /// has already established a more specific resume point.
+    ///
+    /// [`Projector`]: struct.Projector.html
+    /// [`Projection`]: ../../../eventually-core/projection/trait.Projection.html
+    /// [`Projection::project`]: ../../../eventually-core/projection/trait.Projection.html#tymethod.project
+    /// [`EventFilter::matches`]: trait.EventFilter.html#tymethod.matches
+    /// [`EventFilter::select`]: trait.EventFilter.html#method.select
+    /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+    pub fn build_filtered<P, F>(&self, filter: F) -> Projector<P, Store, Subscriber>
+    where
+        P: Projection + Debug + Clone,
+        Store: EventStore<SourceId = P::SourceId, Event = P::Event>,
+        Subscriber: EventSubscriber<SourceId = P::SourceId, Event = P::Event>,
+        <Store as EventStore>::Error: StdError + Send + Sync + 'static,
+        <Subscriber as EventSubscriber>::Error: StdError + Send + Sync + 'static,
+        F: EventFilter<P::SourceId, P::Event> + Send + Sync + 'static,
+    {
+        Projector::new(
+            self.store.clone(),
+            self.subscriber.clone(),
+            self.checkpoint.clone(),
+            Some(Box::new(filter)),
+            self.error_policy.clone(),
+        )
+    }
+}
+
+/// A predicate applied to each event before it reaches
+/// [`Projection::project`], letting a [`Projector`] skip events a
+/// projection does not care about.
+///
+/// A plain closure `Fn(&Persisted<SourceId, Event>) -> bool` implements this
+/// trait already, taking the default [`select`] of `Select::All`. Implement
+/// the trait directly to also override [`select`] and have the coarse part
+/// of the query pushed down to the [`EventStore`].
+///
+/// [`Projection::project`]: ../../../eventually-core/projection/trait.Projection.html#tymethod.project
+/// [`Projector`]: struct.Projector.html
+/// [`select`]: trait.EventFilter.html#method.select
+/// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+pub trait EventFilter<SourceId, Event> {
+    /// Returns whether the given event is relevant to the projection and
+    /// should be passed to [`Projection::project`].
+    ///
+    /// [`Projection::project`]: ../../../eventually-core/projection/trait.Projection.html#tymethod.project
+    fn matches(&self, event: &eventually_core::store::Persisted<SourceId, Event>) -> bool;
+
+    /// The coarsest [`Select`] guaranteed to include every event this filter
+    /// matches, used to narrow down the one-off stream opened by
+    /// [`Projector::run`] where the backend supports it.
+    ///
+    /// Defaults to `Select::All`, meaning the whole store is streamed and
+    /// filtering happens purely through [`matches`].
+    ///
+    /// [`Select`]: ../../../eventually-core/store/enum.Select.html
+    /// [`Projector::run`]: struct.Projector.html#method.run
+    /// [`matches`]: trait.EventFilter.html#tymethod.matches
+    fn select(&self) -> Select {
+        Select::All
+    }
+}
+
+impl<SourceId, Event, F> EventFilter<SourceId, Event> for F
+where
+    F: Fn(&eventually_core::store::Persisted<SourceId, Event>) -> bool,
+{
+    fn matches(&self, event: &eventually_core::store::Persisted<SourceId, Event>) -> bool {
+        (self)(event)
     }
 }
 
@@ -70,10 +500,17 @@ where
 {
     tx: Sender<P>,
     rx: Receiver<P>, // Keep the receiver to be able to clone it in watch().
+    status_tx: Sender<ProjectorStatus>,
+    status_rx: Receiver<ProjectorStatus>, // Keep the receiver to be able to clone it in watch_status().
+    shutdown_tx: Option<Sender<bool>>,
+    shutdown_rx: Receiver<bool>,
     store: Arc<Store>,
     subscriber: Arc<Subscriber>,
     state: P,
     last_sequence_number: AtomicU32,
+    checkpoint: Option<CheckpointConfig>,
+    filter: Option<Box<dyn EventFilter<P::SourceId, P::Event> + Send + Sync>>,
+    error_policy: ErrorPolicy,
     projection: std::marker::PhantomData<P>,
 }
 
@@ -86,17 +523,32 @@ where
     <Store as EventStore>::Error: StdError + Send + Sync + 'static,
     <Subscriber as EventSubscriber>::Error: StdError + Send + Sync + 'static,
 {
-    fn new(store: Arc<Store>, subscriber: Arc<Subscriber>) -> Self {
+    fn new(
+        store: Arc<Store>,
+        subscriber: Arc<Subscriber>,
+        checkpoint: Option<CheckpointConfig>,
+        filter: Option<Box<dyn EventFilter<P::SourceId, P::Event> + Send + Sync>>,
+        error_policy: ErrorPolicy,
+    ) -> Self {
         let state: P = Default::default();
         let (tx, rx) = channel(state.clone());
+        let (status_tx, status_rx) = channel(ProjectorStatus::default());
+        let (shutdown_tx, shutdown_rx) = channel(false);
 
         Self {
             tx,
             rx,
+            status_tx,
+            status_rx,
+            shutdown_tx: Some(shutdown_tx),
+            shutdown_rx,
             store,
             subscriber,
             state,
+            filter,
+            error_policy,
             last_sequence_number: Default::default(),
+            checkpoint,
             projection: std::marker::PhantomData,
         }
     }
@@ -106,11 +558,144 @@ where
         self.rx.clone()
     }
 
+    /// Returns a handle to request a graceful shutdown of [`run`].
+    ///
+    /// Sending `true` through the returned `Sender` makes `run` stop after it
+    /// finishes processing the event it is currently handling, rather than
+    /// abandoning it mid-way.
+    ///
+    /// Can only be called once per `Projector`; subsequent calls panic.
+    ///
+    /// This is the only supported way to stop `run`: it does not also stop
+    /// itself once every external [`watch`] receiver is dropped. `Projector`
+    /// keeps its own internal `Receiver` alive for the lifetime of the
+    /// struct so that [`watch`] can keep handing out clones of it, which
+    /// means the state channel's receiver count never reaches zero while
+    /// `run` is executing -- there is no external-vs-internal distinction
+    /// `Sender::closed()` could key off. A future `tokio` that lets a
+    /// `Sender` mint fresh receivers on demand (removing the need to retain
+    /// one) would make that signal meaningful; until then, [`shutdown_handle`]
+    /// is the one teardown mechanism.
+    ///
+    /// [`run`]: struct.Projector.html#method.run
+    /// [`watch`]: struct.Projector.html#method.watch
+    /// [`shutdown_handle`]: struct.Projector.html#method.shutdown_handle
+    pub fn shutdown_handle(&mut self) -> Sender<bool> {
+        self.shutdown_tx
+            .take()
+            .expect("shutdown_handle() was already called on this Projector")
+    }
+
+    /// Provides a `Stream` that receives the [`ProjectorStatus`], flipping
+    /// from [`ProjectorStatus::CatchingUp`] to [`ProjectorStatus::Live`] at
+    /// the exact point where the one-off stream is exhausted and the
+    /// `Projector` starts tailing the live subscription.
+    ///
+    /// [`ProjectorStatus`]: enum.ProjectorStatus.html
+    /// [`ProjectorStatus::CatchingUp`]: enum.ProjectorStatus.html#variant.CatchingUp
+    /// [`ProjectorStatus::Live`]: enum.ProjectorStatus.html#variant.Live
+    pub fn watch_status(&self) -> impl Stream<Item = ProjectorStatus> {
+        self.status_rx.clone()
+    }
+
+    /// Applies `self.error_policy` to `error`, whether it came from the
+    /// event stream or from a checkpoint flush: `Ok(())` means the error was
+    /// absorbed (skipped or scheduled for retry) and the caller should treat
+    /// this iteration as done, while `Err` means `run` should abort, either
+    /// because the policy is [`ErrorPolicy::Fail`] or because
+    /// [`ErrorPolicy::Retry`] ran out of attempts.
+    ///
+    /// [`ErrorPolicy::Fail`]: enum.ErrorPolicy.html#variant.Fail
+    /// [`ErrorPolicy::Retry`]: enum.ErrorPolicy.html#variant.Retry
+    async fn apply_error_policy(
+        &self,
+        error: anyhow::Error,
+        catching_up: bool,
+        processed_during_catch_up: u32,
+        skipped_count: &mut u32,
+        retried_count: &mut u32,
+        consecutive_errors: &mut u32,
+    ) -> anyhow::Result<()> {
+        match &self.error_policy {
+            ErrorPolicy::Fail => Err(error),
+            ErrorPolicy::Skip { log } => {
+                *skipped_count += 1;
+
+                if let Some(sink) = log {
+                    sink.send(error).await;
+                }
+
+                let _ = self.status_tx.broadcast(derive_status(
+                    catching_up,
+                    processed_during_catch_up,
+                    *skipped_count,
+                    *retried_count,
+                ));
+
+                Ok(())
+            }
+            ErrorPolicy::Retry { max_attempts, backoff } => {
+                *consecutive_errors += 1;
+
+                if retry_attempts_exhausted(*consecutive_errors, *max_attempts) {
+                    return Err(error);
+                }
+
+                *retried_count += 1;
+
+                let _ = self.status_tx.broadcast(derive_status(
+                    catching_up,
+                    processed_during_catch_up,
+                    *skipped_count,
+                    *retried_count,
+                ));
+
+                tokio::time::delay_for(*backoff).await;
+
+                Ok(())
+            }
+        }
+    }
+
     /// Starts the update of the `Projection` by processing all the events
     /// coming from the [`EventStore`].
     ///
+    /// If a [`CheckpointStore`] has been configured on the
+    /// [`ProjectorBuilder`], the last checkpointed sequence number is
+    /// restored first, and the one-off stream resumes right after it instead
+    /// of replaying the whole store.
+    ///
+    /// `run` returns once the stream is exhausted, or as soon as possible
+    /// after a shutdown is requested through [`shutdown_handle`] -- always
+    /// after the event currently being processed has been fully projected
+    /// (and checkpointed, if configured), never mid-way through one.
+    ///
+    /// The configured [`ErrorPolicy`] governs both stream errors and
+    /// checkpoint-store flush failures; either can be skipped or retried
+    /// instead of aborting `run`, depending on the policy in place.
+    ///
     /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+    /// [`CheckpointStore`]: trait.CheckpointStore.html
+    /// [`ProjectorBuilder`]: struct.ProjectorBuilder.html
+    /// [`shutdown_handle`]: struct.Projector.html#method.shutdown_handle
+    /// [`ErrorPolicy`]: enum.ErrorPolicy.html
     pub async fn run(&mut self) -> anyhow::Result<()> {
+        let mut restored_checkpoint = None;
+
+        if let Some(checkpoint) = &self.checkpoint {
+            if let Some(last_sequence_number) = checkpoint.store.load(&checkpoint.projection_id).await {
+                self.last_sequence_number.store(last_sequence_number, Ordering::SeqCst);
+                restored_checkpoint = Some(last_sequence_number);
+            }
+        }
+
+        // NOTE: we can't use `last_sequence_number == 0` as the "no checkpoint
+        // was ever saved" sentinel, since 0 is also a legitimate checkpointed
+        // sequence number (e.g. the very first event in the store). Whether
+        // we resume from a checkpoint is tracked explicitly instead.
+        let filter_select = self.filter.as_ref().map_or(Select::All, |filter| filter.select());
+        let select = resume_select(restored_checkpoint, filter_select);
+
         // Create the Subscription first, so that once the future has been resolved
         // we'll start receiving events right away.
         //
@@ -122,35 +707,571 @@ where
         // keeping an internal state of the last processed sequence number,
         // and discard all those events that are found.
         let subscription = self.subscriber.subscribe_all().await?;
-        let one_off_stream = self.store.stream_all(Select::All).await?;
+        let one_off_stream = self.store.stream_all(select).await?;
+
+        // Insert a sentinel between the one-off and live streams so that
+        // `run` can observe the exact point where catch-up ends, without
+        // having to guess it from sequence numbers (which might legitimately
+        // repeat across the two streams, see the note above).
+        let catch_up_complete = futures::stream::once(async { Ok(StreamItem::CatchUpComplete) });
 
         let mut stream = one_off_stream
+            .map_ok(StreamItem::Event)
             .map_err(anyhow::Error::from)
-            .chain(subscription.map_err(anyhow::Error::from));
+            .chain(catch_up_complete)
+            .chain(subscription.map_ok(StreamItem::Event).map_err(anyhow::Error::from));
+
+        let mut events_since_flush: u32 = 0;
+        let mut last_flush_at = Instant::now();
+        let mut catching_up = true;
+        let mut processed_during_catch_up: u32 = 0;
+        let mut skipped_count: u32 = 0;
+        let mut retried_count: u32 = 0;
+        // Tracked separately from consecutive_checkpoint_errors: a healthy
+        // stream interleaved with a consistently-failing checkpoint flush
+        // must still let ErrorPolicy::Retry give up, so a run of stream
+        // successes must not reset the checkpoint flush's own counter (and
+        // vice versa).
+        let mut consecutive_stream_errors: u32 = 0;
+        let mut consecutive_checkpoint_errors: u32 = 0;
+        let mut shutdown = self.shutdown_rx.clone();
+
+        loop {
+            // select! does not prefer the shutdown branch when the stream is
+            // also ready, so check for a pending shutdown request first.
+            if *shutdown.borrow() {
+                break;
+            }
+
+            let item = tokio::select! {
+                item = stream.next() => item,
+                Some(true) = shutdown.next() => {
+                    // Stop pulling new events, without abandoning anything
+                    // in flight: we only reach here between iterations, once
+                    // the previous event has been fully projected (and
+                    // checkpointed, if configured).
+                    break;
+                }
+            };
+
+            let item = match item {
+                Some(item) => item,
+                None => break,
+            };
+
+            let stream_item = match item {
+                Ok(stream_item) => {
+                    consecutive_stream_errors = 0;
+                    stream_item
+                }
+                Err(error) => {
+                    self.apply_error_policy(
+                        error,
+                        catching_up,
+                        processed_during_catch_up,
+                        &mut skipped_count,
+                        &mut retried_count,
+                        &mut consecutive_stream_errors,
+                    )
+                    .await?;
+
+                    continue;
+                }
+            };
+
+            let event = match stream_item {
+                StreamItem::CatchUpComplete => {
+                    catching_up = false;
+
+                    let _ = self.status_tx.broadcast(derive_status(
+                        catching_up,
+                        processed_during_catch_up,
+                        skipped_count,
+                        retried_count,
+                    ));
+
+                    continue;
+                }
+                StreamItem::Event(event) => event,
+            };
 
-        while let Some(event) = stream.next().await {
-            let event = event?;
             let expected_sequence_number = self.last_sequence_number.load(Ordering::SeqCst);
             let event_sequence_number = event.sequence_number();
 
-            if event_sequence_number < expected_sequence_number {
+            if is_duplicate_sequence(expected_sequence_number, event_sequence_number) {
                 continue; // Duplicated event detected, let's skip it.
             }
 
-            self.state = P::project(self.state.clone(), event);
+            let relevant = self.filter.as_ref().map_or(true, |filter| filter.matches(&event));
+
+            if relevant {
+                self.state = P::project(self.state.clone(), event);
+
+                // Notify watchers of the latest projection state.
+                self.tx.broadcast(self.state.clone()).expect(
+                    "since this struct holds the original receiver, failures should not happen",
+                );
 
+                if catching_up {
+                    processed_during_catch_up += 1;
+
+                    let _ = self.status_tx.broadcast(derive_status(
+                        catching_up,
+                        processed_during_catch_up,
+                        skipped_count,
+                        retried_count,
+                    ));
+                }
+            }
+
+            // Bump the sequence number -- and flush the checkpoint below --
+            // regardless of `relevant`: a filtered-out event still needs to
+            // count towards catch-up/dedup and checkpoint progress, or a
+            // selective Projector's checkpoint would lag arbitrarily far
+            // behind the events it's actually allowed to skip over.
             self.last_sequence_number.compare_and_swap(
                 expected_sequence_number,
                 event_sequence_number,
                 Ordering::SeqCst,
             );
 
-            // Notify watchers of the latest projection state.
-            self.tx.broadcast(self.state.clone()).expect(
-                "since this struct holds the original receiver, failures should not happen",
-            );
+            if let Some(checkpoint) = &self.checkpoint {
+                events_since_flush += 1;
+
+                let should_flush = should_flush_checkpoint(checkpoint.cadence, events_since_flush, last_flush_at);
+
+                if should_flush {
+                    let flushed = checkpoint
+                        .store
+                        .save(&checkpoint.projection_id, event_sequence_number)
+                        .await;
+
+                    match flushed {
+                        Ok(()) => {
+                            events_since_flush = 0;
+                            last_flush_at = Instant::now();
+                            consecutive_checkpoint_errors = 0;
+                        }
+                        // Leave events_since_flush/last_flush_at untouched so
+                        // the next event retries the flush instead of
+                        // silently losing track of how far behind we are.
+                        Err(error) => {
+                            self.apply_error_policy(
+                                error,
+                                catching_up,
+                                processed_during_catch_up,
+                                &mut skipped_count,
+                                &mut retried_count,
+                                &mut consecutive_checkpoint_errors,
+                            )
+                            .await?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+trait GroupedProjection<SourceId, Event>: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    async fn project(&self, event: eventually_core::store::Persisted<SourceId, Event>);
+}
+
+struct ProjectionHandler<P: Projection> {
+    rx: Receiver<P>,
+    // Events are handed off to this handler's own task (spawned in
+    // `register`) instead of being awaited inline, so a slow or stuck
+    // projection only backs up its own queue rather than blocking delivery
+    // of the shared stream to every other registered projection.
+    event_tx: mpsc::UnboundedSender<eventually_core::store::Persisted<P::SourceId, P::Event>>,
+}
+
+#[async_trait::async_trait]
+impl<P> GroupedProjection<P::SourceId, P::Event> for ProjectionHandler<P>
+where
+    P: Projection + Debug + Clone + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn project(&self, event: eventually_core::store::Persisted<P::SourceId, P::Event>) {
+        // The receiving end lives in the task spawned by `register`; if it's
+        // gone the task has already stopped, which mirrors `tx.broadcast`'s
+        // own "only panics while a receiver is retained" expectation.
+        let _ = self.event_tx.send(event);
+    }
+}
+
+/// A `ProjectorGroup` drives several [`Projection`]s off a single catch-up
+/// and live [`EventStore`] stream, instead of each [`Projector`] opening its
+/// own replay and subscription.
+///
+/// Projections are registered with [`register`] and are driven concurrently
+/// for each incoming event, so that a slow projection does not stall the
+/// others. The latest state of a registered projection is obtainable through
+/// [`watch`].
+///
+/// [`Projection`]: ../../../eventually-core/projection/trait.Projection.html
+/// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+/// [`Projector`]: struct.Projector.html
+/// [`register`]: struct.ProjectorGroup.html#method.register
+/// [`watch`]: struct.ProjectorGroup.html#method.watch
+pub struct ProjectorGroup<Store, Subscriber>
+where
+    Store: EventStore,
+{
+    store: Arc<Store>,
+    subscriber: Arc<Subscriber>,
+    handlers: HashMap<TypeId, Box<dyn GroupedProjection<Store::SourceId, Store::Event>>>,
+}
+
+impl<Store, Subscriber> ProjectorGroup<Store, Subscriber>
+where
+    Store: EventStore,
+    Subscriber: EventSubscriber<SourceId = Store::SourceId, Event = Store::Event>,
+    <Store as EventStore>::Error: StdError + Send + Sync + 'static,
+    <Subscriber as EventSubscriber>::Error: StdError + Send + Sync + 'static,
+{
+    /// Creates a new, empty `ProjectorGroup` using the provided [`EventStore`]
+    /// and [`EventSubscriber`].
+    ///
+    /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+    /// [`EventSubscriber`]: ../../../eventually-core/subscription/trait.EventSubscriber.html
+    pub fn new(store: Arc<Store>, subscriber: Arc<Subscriber>) -> Self {
+        Self {
+            store,
+            subscriber,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers a [`Projection`] to be driven by this group.
+    ///
+    /// [`Projection`]: ../../../eventually-core/projection/trait.Projection.html
+    pub fn register<P>(mut self) -> Self
+    where
+        P: Projection<SourceId = Store::SourceId, Event = Store::Event> + Debug + Clone + 'static,
+    {
+        let state: P = Default::default();
+        let (tx, rx) = channel(state.clone());
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+
+        // Owns the projection's state exclusively, so applying events never
+        // contends with other handlers' state and never blocks the stream
+        // driving `ProjectorGroup::run`.
+        tokio::spawn(async move {
+            let mut state = state;
+
+            while let Some(event) = event_rx.recv().await {
+                state = P::project(state, event);
+
+                // Notify watchers of the latest projection state.
+                tx.broadcast(state.clone())
+                    .expect("since this struct holds the original receiver, failures should not happen");
+            }
+        });
+
+        self.handlers.insert(TypeId::of::<P>(), Box::new(ProjectionHandler { rx, event_tx }));
+
+        self
+    }
+
+    /// Provides a `Stream` that receives the latest copy of the state of the
+    /// [`Projection`] `P`, registered previously through [`register`].
+    ///
+    /// Panics if `P` has not been registered on this group.
+    ///
+    /// [`Projection`]: ../../../eventually-core/projection/trait.Projection.html
+    /// [`register`]: struct.ProjectorGroup.html#method.register
+    pub fn watch<P>(&self) -> impl Stream<Item = P>
+    where
+        P: Projection<SourceId = Store::SourceId, Event = Store::Event> + Debug + Clone + 'static,
+    {
+        self.handlers
+            .get(&TypeId::of::<P>())
+            .unwrap_or_else(|| panic!("projection {} was not registered in this group", std::any::type_name::<P>()))
+            .as_any()
+            .downcast_ref::<ProjectionHandler<P>>()
+            .expect("type mismatch in ProjectorGroup handler registry")
+            .rx
+            .clone()
+    }
+
+    /// Starts driving all the registered [`Projection`]s by processing the
+    /// events coming from the single shared [`EventStore`] stream, fanning
+    /// out each event to every handler concurrently.
+    ///
+    /// [`Projection`]: ../../../eventually-core/projection/trait.Projection.html
+    /// [`EventStore`]: ../../../eventually-core/store/trait.EventStore.html
+    pub async fn run(&mut self) -> anyhow::Result<()>
+    where
+        Store::SourceId: Clone,
+        Store::Event: Clone,
+    {
+        // See Projector::run for why the subscription is created before the
+        // one-off stream: we might get duplicated events from the overlap
+        // between the one-off and live streams, which is why we keep track
+        // of the last sequence number fanned out and discard anything
+        // strictly below it, exactly like Projector::run does.
+        let subscription = self.subscriber.subscribe_all().await?;
+        let one_off_stream = self.store.stream_all(Select::All).await?;
+
+        let mut stream = one_off_stream
+            .map_err(anyhow::Error::from)
+            .chain(subscription.map_err(anyhow::Error::from));
+
+        let mut last_sequence_number: u32 = 0;
+
+        while let Some(event) = stream.next().await {
+            let event = event?;
+            let event_sequence_number = event.sequence_number();
+
+            if is_duplicate_sequence(last_sequence_number, event_sequence_number) {
+                continue; // Duplicated event detected, let's skip it.
+            }
+
+            last_sequence_number = event_sequence_number;
+
+            // Handing off to each handler only enqueues the event onto that
+            // handler's own task (see `register`), so a stalled projection
+            // can't stall delivery to the others or to the shared stream.
+            for handler in self.handlers.values() {
+                handler.project(event.clone()).await;
+            }
         }
 
         Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        derive_status, is_duplicate_sequence, resume_select, retry_attempts_exhausted, should_flush_checkpoint,
+        ProjectorBuilder, ProjectorStatus,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn catch_up_live_overlap_is_detected_as_duplicate() {
+        // The live subscription re-delivers everything from the overlap
+        // window with the one-off stream, so anything below the last
+        // sequence number we've already fanned out must be treated as a
+        // duplicate -- this is the exact condition ProjectorGroup::run hits
+        // at the catch-up/live boundary.
+        assert!(is_duplicate_sequence(10, 9));
+        assert!(is_duplicate_sequence(10, 5));
+    }
+
+    #[test]
+    fn events_past_the_last_seen_sequence_are_not_duplicates() {
+        assert!(!is_duplicate_sequence(10, 11));
+        assert!(!is_duplicate_sequence(0, 0)); // first-ever event, nothing seen yet
+    }
+
+    #[test]
+    fn should_flush_checkpoint_honors_the_event_count_cadence() {
+        // The EveryEvents cadence counts events_since_flush, which run() now
+        // bumps for every event that advances last_sequence_number -- not
+        // only the ones that pass the EventFilter -- so a selective
+        // Projector's checkpoint doesn't lag behind events it's allowed to
+        // skip over.
+        use super::FlushCadence;
+
+        assert!(!should_flush_checkpoint(FlushCadence::EveryEvents(3), 2, std::time::Instant::now()));
+        assert!(should_flush_checkpoint(FlushCadence::EveryEvents(3), 3, std::time::Instant::now()));
+    }
+
+    #[test]
+    fn should_flush_checkpoint_honors_the_interval_cadence() {
+        use super::FlushCadence;
+        use std::time::{Duration, Instant};
+
+        let long_ago = Instant::now() - Duration::from_secs(10);
+        assert!(should_flush_checkpoint(FlushCadence::EveryInterval(Duration::from_secs(1)), 0, long_ago));
+
+        let just_now = Instant::now();
+        assert!(!should_flush_checkpoint(FlushCadence::EveryInterval(Duration::from_secs(60)), 0, just_now));
+    }
+
+    #[test]
+    fn derive_status_flips_from_catching_up_to_live_once_catch_up_completes() {
+        // This is the exact transition run() makes at the one-off/live
+        // boundary: `processed` stops being tracked once catching_up flips
+        // to false, and skipped/retried counts carry over unchanged.
+        assert_eq!(
+            derive_status(true, 3, 1, 2),
+            ProjectorStatus::CatchingUp {
+                processed: 3,
+                skipped: 1,
+                retried: 2,
+            }
+        );
+        assert_eq!(derive_status(false, 3, 1, 2), ProjectorStatus::Live { skipped: 1, retried: 2 });
+    }
+
+    #[test]
+    fn resume_select_prefers_the_restored_checkpoint_over_the_filter() {
+        use eventually_core::store::Select;
+
+        assert!(matches!(resume_select(Some(41), Select::All), Select::From(42)));
+    }
+
+    #[test]
+    fn resume_select_falls_back_to_the_filter_select_without_a_checkpoint() {
+        use eventually_core::store::Select;
+
+        assert!(matches!(resume_select(None, Select::All), Select::All));
+        assert!(matches!(resume_select(None, Select::From(7)), Select::From(7)));
+    }
+
+    struct NullCheckpointStore;
+
+    #[async_trait::async_trait]
+    impl super::CheckpointStore for NullCheckpointStore {
+        async fn load(&self, _projection_id: &str) -> Option<u32> {
+            None
+        }
+
+        async fn save(&self, _projection_id: &str, _sequence_number: u32) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn checkpoint_flush_cadence_override_is_order_independent() {
+        let cadence_then_store = ProjectorBuilder::<(), ()>::new(Arc::new(()), Arc::new(()))
+            .with_checkpoint_flush_cadence(super::FlushCadence::EveryEvents(7))
+            .with_checkpoint_store(NullCheckpointStore, "proj");
+
+        let store_then_cadence = ProjectorBuilder::<(), ()>::new(Arc::new(()), Arc::new(()))
+            .with_checkpoint_store(NullCheckpointStore, "proj")
+            .with_checkpoint_flush_cadence(super::FlushCadence::EveryEvents(7));
+
+        assert!(matches!(
+            cadence_then_store.checkpoint.as_ref().unwrap().cadence,
+            super::FlushCadence::EveryEvents(7)
+        ));
+        assert!(matches!(
+            store_then_cadence.checkpoint.as_ref().unwrap().cadence,
+            super::FlushCadence::EveryEvents(7)
+        ));
+    }
+
+    // Substantiates the claim in Projector::shutdown_handle's doc comment:
+    // retaining an internal Receiver (as Projector does, for watch()) means
+    // Sender::closed() never resolves, so it cannot be used to detect "every
+    // external watcher dropped" -- shutdown_handle() is the only way to stop
+    // run().
+    #[tokio::test]
+    async fn sender_closed_never_resolves_while_a_receiver_is_retained() {
+        let (tx, rx) = tokio::sync::watch::channel(0);
+        let _retained = rx.clone();
+        drop(rx);
+
+        tokio::time::timeout(std::time::Duration::from_millis(50), tx.closed())
+            .await
+            .expect_err("closed() resolved even though a clone of the receiver is still alive");
+    }
+
+    #[tokio::test]
+    async fn sender_closed_resolves_once_every_receiver_is_dropped() {
+        let (tx, rx) = tokio::sync::watch::channel(0);
+        drop(rx);
+
+        tokio::time::timeout(std::time::Duration::from_millis(50), tx.closed())
+            .await
+            .expect("closed() did not resolve after the only receiver was dropped");
+    }
+
+    // Mirrors the tokio::select! structure at the top of Projector::run's
+    // loop: the shutdown branch is only polled between iterations, so a
+    // shutdown signaled while an event is being processed must not cut that
+    // event short -- it takes effect only once that event is fully done and
+    // the loop comes back around for the next item.
+    #[tokio::test]
+    async fn shutdown_takes_effect_between_iterations_not_mid_event() {
+        use futures::stream::{self, StreamExt};
+
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
+        let mut items = stream::iter(vec![1, 2, 3]);
+        let mut processed = Vec::new();
+
+        loop {
+            if *shutdown_rx.borrow() {
+                break;
+            }
+
+            let item = tokio::select! {
+                item = items.next() => item,
+                Some(true) = shutdown_rx.next() => break,
+            };
+
+            let item = match item {
+                Some(item) => item,
+                None => break,
+            };
+
+            // Once an item has been taken off the stream, "processing" it
+            // runs to completion here, exactly like event projection and
+            // checkpoint flushing do in the real loop, before shutdown is
+            // checked again.
+            processed.push(item);
+
+            if item == 1 {
+                shutdown_tx.broadcast(true).unwrap();
+            }
+        }
+
+        assert_eq!(processed, vec![1], "the in-flight item must finish processing, but no further item may start");
+    }
+
+    #[test]
+    fn checkpoint_errors_accumulate_independently_of_stream_successes() {
+        // Regression test for the counter-sharing bug: a healthy stream
+        // (which resets a stream-read counter every iteration) must not
+        // reset a *separate* checkpoint-flush counter, or ErrorPolicy::Retry
+        // would never give up on a checkpoint store that keeps failing.
+        let max_attempts = 3;
+        let mut consecutive_stream_errors = 0;
+        let mut consecutive_checkpoint_errors = 0;
+
+        for _ in 0..10 {
+            // Every event: the stream read succeeds...
+            consecutive_stream_errors = 0;
+            assert!(!retry_attempts_exhausted(consecutive_stream_errors, max_attempts));
+
+            // ...but the checkpoint flush keeps failing.
+            consecutive_checkpoint_errors += 1;
+        }
+
+        assert!(retry_attempts_exhausted(consecutive_checkpoint_errors, max_attempts));
+    }
+
+    #[test]
+    fn retry_keeps_going_up_to_max_attempts() {
+        let max_attempts = 3;
+
+        for consecutive_errors in 1..=max_attempts {
+            assert!(
+                !retry_attempts_exhausted(consecutive_errors, max_attempts),
+                "attempt {} should still be retried with max_attempts = {}",
+                consecutive_errors,
+                max_attempts,
+            );
+        }
+    }
+
+    #[test]
+    fn retry_gives_up_once_max_attempts_is_exceeded() {
+        let max_attempts = 3;
+
+        assert!(retry_attempts_exhausted(max_attempts + 1, max_attempts));
+    }
 }
\ No newline at end of file